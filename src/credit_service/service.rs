@@ -1,8 +1,10 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
-use std::{thread, time};
+use std::time::{self, SystemTime, UNIX_EPOCH};
 
+use ethers::abi::RawLog;
 use ethers::prelude::*;
+use tokio::time::sleep;
 
 use crate::ampq_service::AmpqService;
 use crate::bindings::{AddressProvider, Previewer};
@@ -10,11 +12,26 @@ use crate::config::Config;
 use crate::credit_service::FixedLender;
 use crate::errors::LiquidationError;
 use crate::errors::LiquidationError::NetError;
+use crate::exactly_events::ExactlyEvents;
+use crate::fixed_point_math::{FixedPointMath, FixedPointMathGen};
+use crate::health_index::{aggregate_position, event_account, relevant_event, HealthIndex};
 use crate::path_finder::PathFinder;
 use crate::price_oracle::oracle::PriceOracle;
 use crate::terminator_service::terminator::{TerminatorJob, TerminatorService};
 use crate::token_service::service::TokenService;
 
+/// Default number of recent blocks kept to detect and unwind chain reorgs.
+const REORG_BUFFER_SIZE: usize = 256;
+
+/// A minimal header needed to detect a reorg: its own hash and the hash it
+/// claims as its parent, so a new block can be linked back into the buffer.
+#[derive(Clone, Copy, Debug)]
+struct BlockInfo {
+    number: U64,
+    hash: H256,
+    parent_hash: H256,
+}
+
 pub struct CreditService<M: Middleware, S: Signer> {
     credit_managers: Vec<FixedLender<SignerMiddleware<M, S>>>,
     token_service: TokenService<SignerMiddleware<M, S>>,
@@ -32,6 +49,19 @@ pub struct CreditService<M: Middleware, S: Signer> {
     charts_url: String,
     liquidator_enabled: bool,
     config: Config,
+    // Ring buffer of recently synced blocks, oldest first, used to find the
+    // common ancestor when a reorg is detected.
+    block_history: VecDeque<BlockInfo>,
+    // Liquidations only fire on events buried under this many confirmations,
+    // so transient/soon-to-be-reorged state never triggers a liquidation.
+    confirmation_depth: U64,
+    // Minimum net profit (in the repay asset's underlying units) a
+    // simulated liquidation must clear before it gets broadcast.
+    min_profit: U256,
+    // Borrower -> collateral/debt index, seeded once via `Previewer` and
+    // mutated incrementally from decoded events, so only accounts near the
+    // liquidation threshold need recomputing on a new block or price move.
+    health_index: HealthIndex,
 }
 
 impl<M: Middleware, S: Signer> CreditService<M, S> {
@@ -70,6 +100,10 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
             charts_url: config.charts_url.clone(),
             liquidator_enabled: config.liquidator_enabled,
             config: config.clone(),
+            block_history: VecDeque::with_capacity(REORG_BUFFER_SIZE),
+            confirmation_depth: config.confirmation_depth,
+            min_profit: config.min_profit,
+            health_index: HealthIndex::new(),
         }
     }
 
@@ -111,6 +145,13 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
                 Arc::clone(&auditor),
             );
             self.credit_managers.push(fixed_lender);
+
+            // `price_oracle` only prices markets it's been told about via
+            // `set_sources` - without this, `sources` stays empty forever and
+            // `update_prices` has nothing to refresh.
+            if let Some(sources) = self.config.price_sources.get(&market) {
+                self.price_oracle.set_sources(market, sources.clone());
+            }
         }
         // println!("total assets {:?}", total_assets);
         // for cm in cm_list {
@@ -137,9 +178,24 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
         //     .await;
 
         self.last_block_synced = block_number;
+        if let Ok(info) = self.fetch_block_info(block_number).await {
+            self.block_history.push_back(info);
+        }
+
+        self.seed_health_index().await;
 
         self.update().await;
 
+        match &self.config.ws_url {
+            Some(ws_url) => self.watch_via_websocket(ws_url.clone()).await,
+            None => self.watch_via_polling().await,
+        }
+    }
+
+    // Falls back to HTTP block polling, re-scanning the full range on every
+    // new block. Used when no `ws_url` is configured, or when the
+    // WebSocket subscription drops and can't be reconnected.
+    async fn watch_via_polling(&mut self) {
         let watcher = self.client.clone();
         let mut on_block = watcher
             .watch_blocks()
@@ -159,11 +215,152 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
             if !self.liquidator_enabled {
                 println!("zzzzz...");
                 let delay = time::Duration::from_secs(20);
-                thread::sleep(delay);
+                sleep(delay).await;
             }
         }
     }
 
+    // Subscribes to `newHeads` plus logs for the market contracts (see
+    // `event_contract_addresses`): a new head still drives a full `update()` (interest
+    // accrual and terminator jobs are block-interval concerns), while a
+    // relevant log only refreshes the health index from `Previewer`, so a
+    // single position-changing event doesn't pay for a full rescan.
+    // Reconnects with backoff on drop. After `MAX_WS_RETRIES` consecutive
+    // failed (re)connect attempts, gives up on the socket for good and
+    // falls back to HTTP polling, which never gives up.
+    async fn watch_via_websocket(&mut self, ws_url: String) {
+        const MAX_WS_RETRIES: u32 = 5;
+        let mut backoff_secs = 1u64;
+        let mut retries = 0u32;
+        loop {
+            match Provider::<Ws>::connect(ws_url.clone()).await {
+                Ok(ws_provider) => {
+                    backoff_secs = 1;
+                    retries = 0;
+                    let filter = Filter::new().address(self.event_contract_addresses());
+
+                    let (mut heads, mut logs) = match tokio::try_join!(
+                        ws_provider.subscribe_blocks(),
+                        ws_provider.subscribe_logs(&filter),
+                    ) {
+                        Ok((heads, logs)) => (heads.stream(), logs.stream()),
+                        Err(e) => {
+                            println!("ws subscribe failed: {}", e);
+                            sleep(time::Duration::from_secs(backoff_secs)).await;
+                            backoff_secs = (backoff_secs * 2).min(60);
+                            continue;
+                        }
+                    };
+
+                    loop {
+                        tokio::select! {
+                            head = heads.next() => {
+                                if head.is_none() {
+                                    break;
+                                }
+                                self.drive_update().await;
+                            }
+                            log = logs.next() => {
+                                match log {
+                                    None => break,
+                                    Some(log) => self.drive_incremental_update(log).await,
+                                }
+                            }
+                        }
+                    }
+                    println!("ws subscription dropped, reconnecting...");
+                }
+                Err(e) => {
+                    retries += 1;
+                    if retries >= MAX_WS_RETRIES {
+                        println!(
+                            "ws connect failed {} times ({}), falling back to polling",
+                            retries, e
+                        );
+                        return self.watch_via_polling().await;
+                    }
+                    println!("ws connect failed: {}, retrying", e);
+                    sleep(time::Duration::from_secs(backoff_secs)).await;
+                    backoff_secs = (backoff_secs * 2).min(60);
+                }
+            }
+        }
+    }
+
+    async fn drive_update(&mut self) {
+        if let Err(e) = self.update().await {
+            println!("{}", &e);
+            self.ampq_service.send(e.to_string()).await;
+        }
+    }
+
+    // Reacts to a single decoded log without a full rescan: position-moving
+    // events (`event_account` returns the single borrower they affected) are
+    // patched in directly from that borrower's own `Previewer` rows; events
+    // that can reprice a whole market at once fall back to a full
+    // `refresh_from_snapshot`; anything else (informational events the
+    // index doesn't care about) is ignored.
+    async fn drive_incremental_update(&mut self, log: Log) {
+        let event = ExactlyEvents::decode_log(&RawLog {
+            topics: log.topics,
+            data: log.data.to_vec(),
+        });
+        let event = match event {
+            Ok(event) if relevant_event(&event) => event,
+            _ => return,
+        };
+        match event_account(&event) {
+            Some(borrower) => self.update_borrower_position(borrower).await,
+            None => match self.previewer.accounts().call().await {
+                Ok(accounts) => self.health_index.refresh_from_snapshot(accounts),
+                Err(e) => println!("cant refresh health index: {}", e),
+            },
+        }
+    }
+
+    // Patches `borrower`'s tracked position from its own `Previewer` rows
+    // instead of `refresh_from_snapshot`'s full-account rescan, valuing them
+    // the same way `protocol::liquidation::pick_markets` does but at the raw
+    // oracle price, without its `price_spread`.
+    async fn update_borrower_position(&mut self, borrower: Address) {
+        let market_accounts = match self.previewer.exactly(borrower).call().await {
+            Ok(market_accounts) => market_accounts,
+            Err(e) => {
+                println!("cant refresh position for {:?}: {}", borrower, e);
+                return;
+            }
+        };
+        let prices: HashMap<Address, U256> = market_accounts
+            .iter()
+            .filter_map(|market| {
+                self.price_oracle
+                    .price(&market.market)
+                    .map(|price| (market.market, price))
+            })
+            .collect();
+        let timestamp = U256::from(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs(),
+        );
+        let (adjusted_collateral, adjusted_debt) =
+            aggregate_position(&market_accounts, &prices, timestamp);
+        self.health_index
+            .on_position_update(borrower, adjusted_collateral, adjusted_debt);
+    }
+
+    // Addresses whose logs are worth subscribing to incrementally: the
+    // markets themselves (`Borrow`/`Seize`/`MarketEntered`). Price feed
+    // contracts are not included, so oracle updates reach the bot only
+    // through the per-block `update()`.
+    fn event_contract_addresses(&self) -> Vec<Address> {
+        self.credit_managers
+            .iter()
+            .map(|fixed_lender| fixed_lender.contract_address())
+            .collect()
+    }
+
     pub fn get_tokens(&self) -> HashSet<Address> {
         let mut set: HashSet<Address> = HashSet::new();
 
@@ -176,27 +373,120 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
         set
     }
 
+    // One-time seed of the health index from `Previewer`, before
+    // incremental per-event updates take over.
+    async fn seed_health_index(&mut self) {
+        match self.previewer.accounts().call().await {
+            Ok(accounts) => self.health_index.seed(accounts),
+            Err(e) => println!("cant seed health index: {}", e),
+        }
+    }
+
+    // Fetches just enough of a block's header to link it into `block_history`.
+    async fn fetch_block_info(&self, number: U64) -> Result<BlockInfo, LiquidationError> {
+        let block = self
+            .client
+            .provider()
+            .get_block(number)
+            .await
+            .map_err(|r| NetError(format!("cant get block {} ({})", number, r.to_string())))?
+            .ok_or_else(|| NetError(format!("block {} not found", number)))?;
+        Ok(BlockInfo {
+            number,
+            hash: block.hash.unwrap_or_default(),
+            parent_hash: block.parent_hash,
+        })
+    }
+
+    // Walks `block_history` backward from the tip looking for a block whose
+    // hash matches `parent_hash`, i.e. the last block both chains agree on.
+    fn find_common_ancestor(&self, parent_hash: H256) -> Option<U64> {
+        self.block_history
+            .iter()
+            .rev()
+            .find(|info| info.hash == parent_hash)
+            .map(|info| info.number)
+    }
+
+    // Drops any buffered blocks that were orphaned by the reorg and rewinds
+    // `last_block_synced` so the rest of the current `update()` pass
+    // reprocesses from the fork point,
+    // recomputing any `FixedLender` state and `TerminatorJob`s that were
+    // derived from the now-abandoned blocks.
+    fn rollback_to(&mut self, common_ancestor: U64) {
+        self.block_history
+            .retain(|info| info.number <= common_ancestor);
+        for fixed_lender in self.credit_managers.iter_mut() {
+            fixed_lender.rollback_to(common_ancestor);
+        }
+        self.last_block_synced = common_ancestor;
+    }
+
     // Updates information for new blocks
     pub async fn update(&mut self) -> Result<(), LiquidationError> {
-        // Gets the last block
-        let to = self
+        // Gets the last block, then backs off `confirmation_depth` blocks so
+        // we only ever act on state that is unlikely to be reorged away.
+        let chain_tip = self
             .client
             .provider()
             .get_block_number()
             .await
             .map_err(|r| NetError(format!("cant get last block {}", r.to_string())))?;
+        let to = if chain_tip > self.confirmation_depth {
+            chain_tip - self.confirmation_depth
+        } else {
+            U64::zero()
+        };
 
-        if self.last_block_synced == to {
+        if self.last_block_synced >= to {
             return Ok(());
         }
 
+        // Detect a reorg by comparing the parent of the first new block
+        // against what we last recorded for `last_block_synced`.
+        if let Some(expected_parent) = self.block_history.back().copied() {
+            let first_new = self.fetch_block_info(self.last_block_synced + 1).await?;
+            if first_new.parent_hash != expected_parent.hash {
+                match self.find_common_ancestor(first_new.parent_hash) {
+                    Some(common_ancestor) => {
+                        let msg = format!(
+                            "Reorg detected: rolling back from {} to {}",
+                            &self.last_block_synced, &common_ancestor
+                        );
+                        println!("{}", &msg);
+                        self.ampq_service.send(msg).await;
+                        self.rollback_to(common_ancestor);
+                    }
+                    None => {
+                        return Err(NetError(format!(
+                            "reorg deeper than the {}-block history buffer, can't find common ancestor",
+                            REORG_BUFFER_SIZE
+                        )));
+                    }
+                }
+            }
+        }
+
         println!("Updating info from {} to {}", &self.last_block_synced, &to);
 
-        // Load fresh prices from oracle
-        // self.price_oracle.update_prices().await?;
+        // Load fresh prices from oracle, falling back per-market instead of
+        // failing the whole pass when a feed is stale or reverting.
+        self.price_oracle.update_prices().await?;
 
         let mut terminator_jobs: Vec<TerminatorJob> = Vec::new();
 
+        // Borrowers whose last known health factor is at or below 1.0;
+        // passed into each `FixedLender::update` below so it can restrict
+        // its rescan to these accounts instead of re-evaluating every
+        // borrower in every market.
+        let near_threshold = self.health_index.accounts_near_threshold(U256::exp10(18));
+        if !near_threshold.is_empty() {
+            println!(
+                "{} accounts near the liquidation threshold",
+                near_threshold.len()
+            );
+        }
+
         // Updates info
         for fixed_lender in self.credit_managers.iter_mut() {
             fixed_lender
@@ -205,9 +495,56 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
                     &to,
                     &self.price_oracle,
                     &self.path_finder,
+                    &near_threshold,
                     &mut terminator_jobs,
                 )
-                .await?
+                .await?;
+
+            if !near_threshold.is_empty() {
+                let timestamp = U256::from(
+                    SystemTime::now()
+                        .duration_since(UNIX_EPOCH)
+                        .unwrap()
+                        .as_secs(),
+                );
+                let market = fixed_lender.market();
+                let total_borrowed =
+                    market.borrow_shares_to_assets(market.floating_borrow_shares, timestamp);
+                let max_repay = market.max_repay_assets(total_borrowed, false);
+                // `min_profit` is a token-unit amount, not a WAD ratio, so it
+                // can't be added straight onto the WAD-scaled incentive
+                // multiplier `describe_liquidation_window` expects. Express
+                // it as the bonus-over-par this market's largest repayable
+                // position would need to clear it instead.
+                let required_incentive = U256::exp10(18)
+                    + if max_repay.is_zero() {
+                        U256::zero()
+                    } else {
+                        self.min_profit.mul_div_up(U256::exp10(18), max_repay)
+                    };
+                let window = fixed_lender.market().describe_liquidation_window(
+                    timestamp,
+                    U256::zero(),
+                    required_incentive,
+                );
+                match window.profitable_at {
+                    Some(elapsed) if elapsed.is_zero() => println!(
+                        "market {:?}: up to {:?} repayable now (${:?}), incentive already covers required_incentive",
+                        fixed_lender.contract_address(),
+                        window.max_repay,
+                        window.max_repay_value
+                    ),
+                    Some(elapsed) => println!(
+                        "market {:?}: incentive covers required_incentive after {:?}s unhealthy",
+                        fixed_lender.contract_address(),
+                        elapsed
+                    ),
+                    None => println!(
+                        "market {:?}: even the fully-ramped incentive can't cover required_incentive",
+                        fixed_lender.contract_address()
+                    ),
+                }
+            }
         }
 
         println!("Terminator jobs : {}", &terminator_jobs.len());
@@ -239,6 +576,20 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
                 println!("{}", &msg);
                 self.ampq_service.send(msg).await;
 
+                // Dry-run the liquidation before burning gas on it: skip
+                // jobs that would revert or that don't clear min_profit
+                // once the swap path and gas bid are accounted for.
+                if let Err(reason) = self
+                    .terminator_service
+                    .simulate(job, terminator_type, &self.path_finder, self.min_profit)
+                    .await
+                {
+                    let msg = format!("Skipping liquidation for {:?}: {}", &job.borrower, reason);
+                    println!("{}", &msg);
+                    self.ampq_service.send(msg).await;
+                    continue;
+                }
+
                 let receipt = self
                     .terminator_service
                     .liquidate(job, terminator_type)
@@ -247,12 +598,13 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
                 match receipt {
                     Ok(receipt) => {
                         msg = format!(
-                            "{} account {:?} was successfully liquidated. TxHash: {}/tx/{:?} . Gas used: {:?}\nBlock number: {}",
+                            "{} account {:?} was successfully liquidated. TxHash: {}/tx/{:?} . Gas used: {:?} @ {:?} wei/gas\nBlock number: {}",
                             self.token_service.symbol(&job.underlying_token),
                             &job.borrower,
                             &self.etherscan,
                             &receipt.transaction_hash,
                             &receipt.gas_used.unwrap(),
+                            &receipt.effective_gas_price.unwrap_or_default(),
                             &receipt.block_number.unwrap().as_u64()
                         );
                     }
@@ -281,6 +633,18 @@ impl<M: Middleware, S: Signer> CreditService<M, S> {
             }
         }
 
+        // Record the newly synced blocks so a future reorg can be linked
+        // back into a known ancestor, trimming to the buffer capacity.
+        let mut block = self.last_block_synced + 1;
+        while block <= to {
+            let info = self.fetch_block_info(block).await?;
+            self.block_history.push_back(info);
+            block += U64::one();
+        }
+        while self.block_history.len() > REORG_BUFFER_SIZE {
+            self.block_history.pop_front();
+        }
+
         // Updates the last block synced
         self.last_block_synced = to;
         Ok(())
@@ -0,0 +1,99 @@
+use ethers::types::{I256, U256};
+
+/// WAD-scale constants shared across the liquidation math.
+pub mod math {
+    use ethers::types::U256;
+
+    pub const WAD: U256 = U256([1_000_000_000_000_000_000u64, 0, 0, 0]);
+}
+
+/// Generic (denominator-agnostic) fixed-point primitives: multiply-then-
+/// divide by an arbitrary denominator, and the natural log of a WAD value.
+pub trait FixedPointMathGen {
+    fn mul_div_down(self, y: U256, denominator: U256) -> U256;
+    fn mul_div_up(self, y: U256, denominator: U256) -> U256;
+    /// Natural log of `self`, a WAD (1e18) fixed-point value, itself
+    /// returned as a WAD fixed-point value. Signed because `ln(x) < 0`
+    /// whenever `x < WAD`, which `floating_borrow_rate`'s callers rely on.
+    fn ln_wad(self) -> I256;
+}
+
+/// WAD (1e18) fixed-point convenience wrappers built on `FixedPointMathGen`.
+pub trait FixedPointMath: FixedPointMathGen + Sized {
+    fn mul_wad_down(self, y: U256) -> U256;
+    fn mul_wad_up(self, y: U256) -> U256;
+    fn div_wad_down(self, y: U256) -> U256;
+    fn div_wad_up(self, y: U256) -> U256;
+}
+
+impl FixedPointMathGen for U256 {
+    fn mul_div_down(self, y: U256, denominator: U256) -> U256 {
+        self * y / denominator
+    }
+
+    fn mul_div_up(self, y: U256, denominator: U256) -> U256 {
+        let product = self * y;
+        if product.is_zero() {
+            U256::zero()
+        } else {
+            (product - U256::one()) / denominator + U256::one()
+        }
+    }
+
+    fn ln_wad(self) -> I256 {
+        // Port of solmate's `lnWad`: normalize `self` to the mantissa
+        // `m` in `[2^59, 2^60)` (about `[0.58, 1.15)` as a WAD value) by
+        // tracking the power-of-two `shift`
+        // pulled out of it, then approximate `ln(m)` with the artanh
+        // series `ln(m) = 2*atanh((m-WAD)/(m+WAD))`, and add back
+        // `shift * ln(2)` for the part the normalization removed.
+        assert!(!self.is_zero(), "ln_wad: undefined for 0");
+        const LN2_WAD: i128 = 693_147_180_559_945_309;
+        let wad = I256::from_raw(U256::exp10(18));
+
+        let bits = self.bits();
+        let shift = bits.saturating_sub(1) as i64 - 59; // keep ~60 bits of precision in `m`
+        let m = if shift >= 0 {
+            I256::from_raw(self >> (shift as usize))
+        } else {
+            I256::from_raw(self << ((-shift) as usize))
+        };
+
+        let z = (m - wad) * wad / (m + wad);
+        let z2 = z * z / wad;
+        let mut series = z;
+        let mut term = z;
+        for n in [3i128, 5, 7, 9] {
+            term = term * z2 / wad;
+            series += term / I256::from(n);
+        }
+
+        series * I256::from(2) + I256::from(shift) * I256::from(LN2_WAD)
+    }
+}
+
+impl FixedPointMath for U256 {
+    fn mul_wad_down(self, y: U256) -> U256 {
+        self.mul_div_down(y, U256::exp10(18))
+    }
+
+    fn mul_wad_up(self, y: U256) -> U256 {
+        self.mul_div_up(y, U256::exp10(18))
+    }
+
+    fn div_wad_down(self, y: U256) -> U256 {
+        self.mul_div_down(U256::exp10(18), y)
+    }
+
+    fn div_wad_up(self, y: U256) -> U256 {
+        self.mul_div_up(U256::exp10(18), y)
+    }
+}
+
+/// `x * y / denominator`, rounded toward zero, for the one call site
+/// (`Market::floating_borrow_rate`) that mixes a `U256` magnitude with an
+/// `I256` rate term — `FixedPointMathGen` stays `U256`-only since every
+/// other caller in the crate works in unsigned WAD amounts.
+pub fn mul_div_down_signed(x: U256, y: I256, denominator: I256) -> I256 {
+    I256::from_raw(x) * y / denominator
+}
@@ -0,0 +1,246 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+use ethers::types::{Address, U256};
+
+use crate::bindings::MarketAccount;
+use crate::exactly_events::ExactlyEvents;
+use crate::fixed_point_math::{FixedPointMath, FixedPointMathGen};
+
+/// A borrower's aggregate position across every market it participates in.
+#[derive(Clone, Copy, Default, Debug)]
+struct Position {
+    adjusted_collateral: U256,
+    adjusted_debt: U256,
+}
+
+impl Position {
+    // health factor in WAD, capped at U256::MAX when debt-free so it never
+    // sorts as the most urgent account.
+    fn health_factor(&self) -> U256 {
+        if self.adjusted_debt.is_zero() {
+            U256::MAX
+        } else {
+            self.adjusted_collateral.div_wad_down(self.adjusted_debt)
+        }
+    }
+}
+
+/// Maintains borrower -> collateral/debt incrementally from decoded events
+/// instead of re-evaluating every borrower on every block, and keeps them
+/// ordered by health factor so only accounts near the liquidation threshold
+/// get recomputed and fed into `terminator_jobs`.
+#[derive(Default)]
+pub struct HealthIndex {
+    positions: HashMap<Address, Position>,
+    // Min-heap on health factor: the lowest (most liquidatable) health
+    // factor is always at the top. Entries may be stale if a borrower's
+    // position changed since they were pushed; `accounts_near_threshold`
+    // re-checks against `positions` before trusting a popped entry.
+    by_health: BinaryHeap<Reverse<(U256, Address)>>,
+}
+
+impl HealthIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// One-time seed from `Previewer`, before incremental updates take over.
+    pub fn seed(&mut self, accounts: Vec<(Address, U256, U256)>) {
+        for (borrower, adjusted_collateral, adjusted_debt) in accounts {
+            self.upsert(
+                borrower,
+                Position {
+                    adjusted_collateral,
+                    adjusted_debt,
+                },
+            );
+        }
+    }
+
+    fn upsert(&mut self, borrower: Address, position: Position) {
+        let health_factor = position.health_factor();
+        self.positions.insert(borrower, position);
+        self.by_health.push(Reverse((health_factor, borrower)));
+    }
+
+    /// Mutates `borrower`'s tracked position from a single decoded event.
+    /// Deposit/Withdraw/Borrow/Repay/Seize/MarketEntered/MarketExited all
+    /// move collateral or debt; AdjustFactorSet/PriceFeedSet change how an
+    /// existing balance is valued. All of them require knowing the
+    /// resulting adjusted collateral/debt value, which is cheap to look up
+    /// for a single account/market pair but is out of scope for a bare
+    /// event - callers recompute it and pass it through `on_position_update`.
+    pub fn on_position_update(
+        &mut self,
+        borrower: Address,
+        adjusted_collateral: U256,
+        adjusted_debt: U256,
+    ) {
+        self.upsert(
+            borrower,
+            Position {
+                adjusted_collateral,
+                adjusted_debt,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, borrower: &Address) {
+        self.positions.remove(borrower);
+    }
+
+    /// Incrementally reconciles the index against a fresh `Previewer`
+    /// snapshot: upserts every reported account through `on_position_update`
+    /// and `remove`s any previously-tracked borrower missing from it (fully
+    /// repaid or exited every market). Unlike `seed`, which only upserts,
+    /// this also drops borrowers missing from the snapshot — used as the
+    /// fallback for events `event_account`
+    /// can't attribute to a single borrower (an adjust factor or price feed
+    /// change, which can reprice every holder of that market at once).
+    pub fn refresh_from_snapshot(&mut self, accounts: Vec<(Address, U256, U256)>) {
+        let fresh: HashMap<Address, (U256, U256)> = accounts
+            .into_iter()
+            .map(|(borrower, adjusted_collateral, adjusted_debt)| {
+                (borrower, (adjusted_collateral, adjusted_debt))
+            })
+            .collect();
+        let stale: Vec<Address> = self
+            .positions
+            .keys()
+            .filter(|borrower| !fresh.contains_key(borrower))
+            .copied()
+            .collect();
+        for borrower in stale {
+            self.remove(&borrower);
+        }
+        for (borrower, (adjusted_collateral, adjusted_debt)) in fresh {
+            self.on_position_update(borrower, adjusted_collateral, adjusted_debt);
+        }
+    }
+
+    /// Pops every account whose last known health factor is at or below
+    /// `threshold`, skipping stale heap entries whose position has since
+    /// moved away from the top. Popped accounts are the only ones that need
+    /// to be recomputed and fed into `terminator_jobs` this round, but they
+    /// stay at or below `threshold` until some later event moves them, so
+    /// they're pushed back once the scan is done - otherwise a borrower
+    /// that's still unhealthy next round would no longer be in the heap at
+    /// all and would never be surfaced again.
+    pub fn accounts_near_threshold(&mut self, threshold: U256) -> Vec<Address> {
+        let mut result = Vec::new();
+        let mut popped = Vec::new();
+        while let Some(Reverse((health_factor, borrower))) = self.by_health.peek().copied() {
+            let current = match self.positions.get(&borrower) {
+                Some(position) => position.health_factor(),
+                None => {
+                    self.by_health.pop();
+                    continue;
+                }
+            };
+            if current != health_factor {
+                // stale entry: push the fresh value back and keep looking.
+                self.by_health.pop();
+                self.by_health.push(Reverse((current, borrower)));
+                continue;
+            }
+            if current > threshold {
+                break;
+            }
+            self.by_health.pop();
+            popped.push(Reverse((current, borrower)));
+            result.push(borrower);
+        }
+        self.by_health.extend(popped);
+        result
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+}
+
+/// The single account a decoded event can have moved, when there is one.
+/// `drive_incremental_update` uses this to patch just that borrower via
+/// `on_position_update` instead of paying for a full `Previewer` rescan.
+/// Events that can reprice more than one account at once (an adjust
+/// factor or price feed change) return `None` so the caller falls back to
+/// `refresh_from_snapshot`.
+pub fn event_account(event: &ExactlyEvents) -> Option<Address> {
+    match event {
+        ExactlyEvents::Deposit(e) => Some(e.owner),
+        ExactlyEvents::Withdraw(e) => Some(e.owner),
+        ExactlyEvents::DepositAtMaturity(e) => Some(e.owner),
+        ExactlyEvents::WithdrawAtMaturity(e) => Some(e.owner),
+        ExactlyEvents::Borrow(e) => Some(e.borrower),
+        ExactlyEvents::Repay(e) => Some(e.borrower),
+        ExactlyEvents::BorrowAtMaturity(e) => Some(e.borrower),
+        ExactlyEvents::RepayAtMaturity(e) => Some(e.borrower),
+        ExactlyEvents::Seize(e) => Some(e.borrower),
+        ExactlyEvents::MarketEntered(e) => Some(e.account),
+        ExactlyEvents::MarketExited(e) => Some(e.account),
+        ExactlyEvents::AdjustFactorSet(_) | ExactlyEvents::PriceFeedSetFilter(_) => None,
+        _ => None,
+    }
+}
+
+/// Sums a single borrower's `Previewer` rows into the same adjusted
+/// collateral/debt totals `protocol::liquidation::pick_markets` computes,
+/// except that prices are used as given, with no `price_spread` applied, so `drive_incremental_update` can patch one account into the index
+/// without re-deriving the valuation rules in a second place.
+pub fn aggregate_position(
+    market_accounts: &[MarketAccount],
+    prices: &HashMap<Address, U256>,
+    timestamp: U256,
+) -> (U256, U256) {
+    let mut adjusted_collateral = U256::zero();
+    let mut adjusted_debt = U256::zero();
+    for market in market_accounts {
+        let price = match prices.get(&market.market) {
+            Some(price) => *price,
+            None => continue,
+        };
+        if market.is_collateral {
+            let collateral_value = market
+                .floating_deposit_assets
+                .mul_div_down(price, U256::exp10(market.decimals as usize));
+            adjusted_collateral += collateral_value.mul_wad_down(market.adjust_factor.into());
+        }
+        let mut market_debt_assets = market.floating_borrow_assets;
+        for fixed_position in &market.fixed_borrow_positions {
+            let borrowed = fixed_position.position.principal + fixed_position.position.fee;
+            market_debt_assets += borrowed;
+            if U256::from(fixed_position.maturity) < timestamp {
+                market_debt_assets += borrowed.mul_wad_down(
+                    (timestamp - U256::from(fixed_position.maturity))
+                        * U256::from(market.penalty_rate),
+                );
+            }
+        }
+        let market_debt_value =
+            market_debt_assets.mul_div_up(price, U256::exp10(market.decimals as usize));
+        adjusted_debt += market_debt_value.div_wad_up(market.adjust_factor.into());
+    }
+    (adjusted_collateral, adjusted_debt)
+}
+
+/// Whether a decoded event is worth reacting to at all - anything else is
+/// informational and the index leaves it alone.
+pub fn relevant_event(event: &ExactlyEvents) -> bool {
+    matches!(
+        event,
+        ExactlyEvents::Deposit(_)
+            | ExactlyEvents::Withdraw(_)
+            | ExactlyEvents::Borrow(_)
+            | ExactlyEvents::Repay(_)
+            | ExactlyEvents::DepositAtMaturity(_)
+            | ExactlyEvents::WithdrawAtMaturity(_)
+            | ExactlyEvents::BorrowAtMaturity(_)
+            | ExactlyEvents::RepayAtMaturity(_)
+            | ExactlyEvents::Seize(_)
+            | ExactlyEvents::MarketEntered(_)
+            | ExactlyEvents::MarketExited(_)
+            | ExactlyEvents::AdjustFactorSet(_)
+            | ExactlyEvents::PriceFeedSetFilter(_)
+    )
+}
@@ -7,7 +7,7 @@ use ethers::prelude::{abigen, Middleware, Signer, SignerMiddleware, U256};
 use ethers::types::I256;
 use serde::{Deserialize, Serialize};
 
-use super::fixed_point_math::{FixedPointMath, FixedPointMathGen};
+use super::fixed_point_math::{mul_div_down_signed, FixedPointMath, FixedPointMathGen};
 
 const INTERVAL: u32 = 4 * 7 * 86_400;
 
@@ -49,6 +49,9 @@ pub struct PriceFeedController {
     pub main_price_feed: Option<Box<PriceFeedController>>,
     pub event_emitters: Vec<Address>,
     pub wrapper: Option<PriceFeedType>,
+    /// Last reported price for a plain (unwrapped) feed, used as-is when
+    /// there's no `wrapper` to apply on top of it.
+    pub main_price: U256,
 }
 
 impl PriceFeedController {
@@ -58,8 +61,45 @@ impl PriceFeedController {
             main_price_feed: None,
             event_emitters: event_emitters.unwrap_or_default(),
             wrapper: None,
+            main_price: U256::zero(),
         }
     }
+
+    /// Resolves the final price by recursing through `main_price_feed` and
+    /// applying whatever wrapper sits on top of it: a plain feed returns
+    /// `main_price`, a rate wrapper (liquid-staking/wrapped-token exchange
+    /// rates) scales the upstream price by `rate/base_unit`, and a double
+    /// feed scales the upstream price by the WAD-normalized combination of
+    /// its two Chainlink prices, the same way `Single` scales it by a
+    /// single rate.
+    pub fn price(&self) -> U256 {
+        if let Some(main) = &self.main_price_feed {
+            let upstream = main.price();
+            return match &self.wrapper {
+                Some(PriceFeedType::Single(rate)) => {
+                    upstream.mul_div_down(rate.rate, rate.base_unit)
+                }
+                Some(PriceFeedType::Double(double)) => {
+                    upstream.mul_div_down(Self::resolve_double(double), U256::exp10(18))
+                }
+                None => upstream,
+            };
+        }
+        match &self.wrapper {
+            Some(PriceFeedType::Single(rate)) => {
+                rate.main_price.mul_div_down(rate.rate, rate.base_unit)
+            }
+            Some(PriceFeedType::Double(double)) => Self::resolve_double(double),
+            None => self.main_price,
+        }
+    }
+
+    fn resolve_double(double: &PriceDouble) -> U256 {
+        double
+            .price_one
+            .mul_div_down(double.price_two, U256::exp10(double.decimals.as_usize()))
+            .mul_div_down(double.base_unit, U256::exp10(18))
+    }
 }
 
 #[derive(Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
@@ -99,6 +139,25 @@ pub struct Market {
     pub treasury_fee_rate: U256,
     pub asset: Address,
     pub base_market: bool,
+    /// Fraction of an unhealthy position's debt in this market that a
+    /// single liquidation may repay, in WAD (default 0.5e18).
+    pub close_factor: U256,
+    /// If the debt remaining after applying `close_factor` would fall below
+    /// this amount, the whole position is repaid instead, so it never gets
+    /// stranded as uncollectible dust.
+    pub close_amount: U256,
+    /// Haircut applied to the oracle price when judging liquidation
+    /// eligibility, in WAD (e.g. 2e16 for a 2% spread). Collateral is
+    /// valued below the oracle price and debt above it, so the bot only
+    /// acts once a position is unhealthy by a margin.
+    pub price_spread: U256,
+    /// Liquidation incentive (in WAD, 1e18 = no bonus) offered the instant
+    /// a position becomes unhealthy, before the Dutch auction ramps up.
+    pub auction_start_incentive: U256,
+    /// Seconds over which the incentive ramps linearly from
+    /// `auction_start_incentive` up to the full `penalty_rate`-derived
+    /// bonus.
+    pub auction_window: U256,
 }
 
 impl Eq for Market {}
@@ -139,6 +198,11 @@ impl Market {
             treasury_fee_rate: Default::default(),
             asset: Default::default(),
             base_market: false,
+            close_factor: U256::exp10(18) / 2u8,
+            close_amount: Default::default(),
+            price_spread: Default::default(),
+            auction_start_incentive: U256::exp10(18),
+            auction_window: Default::default(),
         }
     }
 
@@ -208,7 +272,8 @@ impl Market {
                     / 6u8,
             )
         } else {
-            self.floating_a.mul_div_down(
+            mul_div_down_signed(
+                self.floating_a,
                 alpha
                     .div_wad_down(self.floating_max_utilization - utilization_after)
                     .ln_wad(),
@@ -236,4 +301,156 @@ impl Market {
         );
         self.floating_debt + new_debt
     }
+
+    /// ERC-4626 `convertToAssets`: the underlying-asset value of
+    /// `shares` deposit shares at `timestamp`. Rounds down, since this
+    /// values what a holder could redeem.
+    pub fn convert_to_assets(&self, shares: U256, timestamp: U256) -> U256 {
+        if self.floating_deposit_shares.is_zero() {
+            shares
+        } else {
+            shares.mul_div_down(self.total_assets(timestamp), self.floating_deposit_shares)
+        }
+    }
+
+    /// ERC-4626 `convertToShares`: how many deposit shares `assets` is
+    /// worth at `timestamp`.
+    pub fn convert_to_shares(&self, assets: U256, timestamp: U256) -> U256 {
+        let total_assets = self.total_assets(timestamp);
+        if total_assets.is_zero() {
+            assets
+        } else {
+            assets.mul_div_down(self.floating_deposit_shares, total_assets)
+        }
+    }
+
+    /// Underlying-asset value of `shares` floating borrow shares at
+    /// `timestamp`. Rounds up, since this values owed debt.
+    pub fn borrow_shares_to_assets(&self, shares: U256, timestamp: U256) -> U256 {
+        if self.floating_borrow_shares.is_zero() {
+            shares
+        } else {
+            shares.mul_div_up(
+                self.total_floating_borrow_assets(timestamp),
+                self.floating_borrow_shares,
+            )
+        }
+    }
+
+    /// How many floating borrow shares `assets` of debt is worth at
+    /// `timestamp`. Rounds up, since this values owed debt.
+    pub fn borrow_assets_to_shares(&self, assets: U256, timestamp: U256) -> U256 {
+        let total_borrow_assets = self.total_floating_borrow_assets(timestamp);
+        if total_borrow_assets.is_zero() {
+            assets
+        } else {
+            assets.mul_div_up(self.floating_borrow_shares, total_borrow_assets)
+        }
+    }
+
+    /// Oracle price haircut for valuing this market's collateral: below
+    /// the oracle price by `price_spread`, so noisy mid-price swings don't
+    /// make a marginally-healthy position look liquidatable.
+    pub fn collateral_price(&self) -> U256 {
+        self.price.mul_wad_down(U256::exp10(18) - self.price_spread)
+    }
+
+    /// Oracle price markup for valuing this market's debt: above the
+    /// oracle price by `price_spread`, mirroring `collateral_price`.
+    pub fn debt_price(&self) -> U256 {
+        self.price.mul_wad_up(U256::exp10(18) + self.price_spread)
+    }
+
+    /// Dutch-auction liquidation incentive at `elapsed` seconds since the
+    /// account became unhealthy: ramps linearly from
+    /// `auction_start_incentive` up to the full `penalty_rate`-derived
+    /// bonus over `auction_window`, then stays clamped at the maximum.
+    /// Monotonic non-decreasing by construction.
+    pub fn liquidation_incentive(&self, elapsed: U256) -> U256 {
+        let max_incentive = U256::exp10(18) + self.penalty_rate;
+        if self.auction_window.is_zero() || elapsed >= self.auction_window {
+            return max_incentive;
+        }
+        let start_incentive = U256::min(self.auction_start_incentive, max_incentive);
+        start_incentive
+            + (max_incentive - start_incentive).mul_div_down(elapsed, self.auction_window)
+    }
+
+    /// The earliest `elapsed` at which `liquidation_incentive` covers
+    /// `required_incentive` (estimated gas + slippage + `min_profit`,
+    /// expressed the same way as the incentive itself), or `None` if even
+    /// the fully-ramped incentive can't cover it. Found by inverting the
+    /// linear ramp rather than waiting for it, since it's monotonic.
+    pub fn earliest_profitable_elapsed(&self, required_incentive: U256) -> Option<U256> {
+        let max_incentive = U256::exp10(18) + self.penalty_rate;
+        if required_incentive > max_incentive {
+            return None;
+        }
+        let start_incentive = U256::min(self.auction_start_incentive, max_incentive);
+        if required_incentive <= start_incentive || self.auction_window.is_zero() {
+            return Some(U256::zero());
+        }
+        Some(
+            (required_incentive - start_incentive)
+                .mul_div_up(self.auction_window, max_incentive - start_incentive),
+        )
+    }
+
+    /// How much of `account_borrowed` a single liquidation may repay in
+    /// this market: at most `close_factor` of the outstanding borrow while
+    /// the account is unhealthy, or the whole position if what would be
+    /// left behind is below `close_amount` (and so not worth a second
+    /// liquidation to clean up).
+    pub fn max_repay_assets(&self, account_borrowed: U256, account_healthy: bool) -> U256 {
+        if account_healthy {
+            return U256::zero();
+        }
+        let partial_repay = account_borrowed.mul_wad_down(self.close_factor);
+        let remaining = account_borrowed - partial_repay;
+        if remaining < self.close_amount || account_borrowed < self.close_amount {
+            account_borrowed
+        } else {
+            partial_repay
+        }
+    }
+
+    /// Snapshot of what liquidating the largest possible position in this
+    /// market would look like right now: the close-factor-bounded repay
+    /// size against the market's total outstanding floating debt, its
+    /// spread-adjusted USD value, and whether `elapsed_unhealthy` has
+    /// already ramped the Dutch-auction incentive past `required_incentive`.
+    /// Ties together `max_repay_assets`, `borrow_shares_to_assets`,
+    /// `debt_price`/`collateral_price` and `liquidation_incentive`/
+    /// `earliest_profitable_elapsed` into the one operational question the
+    /// bot cares about: repay how much, worth how much, and fire now or
+    /// wait.
+    pub fn describe_liquidation_window(
+        &self,
+        timestamp: U256,
+        elapsed_unhealthy: U256,
+        required_incentive: U256,
+    ) -> LiquidationWindow {
+        let total_borrowed = self.borrow_shares_to_assets(self.floating_borrow_shares, timestamp);
+        let max_repay = self.max_repay_assets(total_borrowed, false);
+        LiquidationWindow {
+            max_repay,
+            max_repay_value: max_repay.mul_wad_up(self.debt_price()),
+            collateral_price: self.collateral_price(),
+            current_incentive: self.liquidation_incentive(elapsed_unhealthy),
+            profitable_at: self.earliest_profitable_elapsed(required_incentive),
+        }
+    }
+}
+
+/// Result of `Market::describe_liquidation_window`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LiquidationWindow {
+    pub max_repay: U256,
+    pub max_repay_value: U256,
+    pub collateral_price: U256,
+    pub current_incentive: U256,
+    /// `elapsed_unhealthy` at which the Dutch-auction incentive would cover
+    /// the caller's `required_incentive`; `None` if even the fully-ramped
+    /// incentive can't.
+    pub profitable_at: Option<U256>,
 }
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ethers::prelude::*;
+use ethers::types::{Address, U256};
+
+use crate::bindings::{ChainlinkFeed, DexTwap};
+use crate::errors::LiquidationError;
+use crate::errors::LiquidationError::NetError;
+
+/// A single feed a market can be priced from, tried in order until one is
+/// fresh. `max_age_secs` bounds how stale the feed's `updated_at` may be;
+/// `max_rounds_behind` bounds how far `round_id` may lag `latest_round_id`.
+#[derive(Clone, Debug)]
+pub enum PriceSource {
+    Chainlink {
+        feed: Address,
+        max_age_secs: u64,
+        max_rounds_behind: u64,
+    },
+    DexTwap {
+        pool: Address,
+        window_secs: u64,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct CachedPrice {
+    price: U256,
+    updated_at: u64,
+}
+
+pub struct PriceOracle<M: Middleware, S: Signer> {
+    client: Arc<SignerMiddleware<M, S>>,
+    // Ordered fallback chain per market: primary Chainlink feed first, then
+    // whatever backups (e.g. a DEX TWAP) are configured for that market.
+    sources: HashMap<Address, Vec<PriceSource>>,
+    prices: HashMap<Address, CachedPrice>,
+}
+
+impl<M: 'static + Middleware, S: 'static + Signer> PriceOracle<M, S> {
+    pub fn new(client: Arc<SignerMiddleware<M, S>>) -> Self {
+        Self {
+            client,
+            sources: HashMap::new(),
+            prices: HashMap::new(),
+        }
+    }
+
+    pub fn set_sources(&mut self, market: Address, sources: Vec<PriceSource>) {
+        self.sources.insert(market, sources);
+    }
+
+    /// Refreshes every configured market, trying each source in order and
+    /// falling back to the next one when the current price is stale or the
+    /// call reverts. A market with no fresh source this round just keeps its
+    /// last cached price rather than failing the whole update pass; `price`
+    /// returns `None` if no source has ever returned a fresh value, which
+    /// callers should treat as "skip this market" rather than an error.
+    pub async fn update_prices(&mut self) -> Result<(), LiquidationError> {
+        let markets: Vec<Address> = self.sources.keys().copied().collect();
+        for market in markets {
+            if let Some(fresh) = self.fetch_fresh_price(&market).await {
+                self.prices.insert(market, fresh);
+            }
+        }
+        Ok(())
+    }
+
+    async fn fetch_fresh_price(&self, market: &Address) -> Option<CachedPrice> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        for source in self.sources.get(market)? {
+            let fresh = match source {
+                PriceSource::Chainlink {
+                    feed,
+                    max_age_secs,
+                    max_rounds_behind,
+                } => self
+                    .read_chainlink(*feed, now, *max_age_secs, *max_rounds_behind)
+                    .await
+                    .ok(),
+                PriceSource::DexTwap { pool, window_secs } => {
+                    self.read_dex_twap(*pool, *window_secs).await.ok()
+                }
+            };
+            if let Some(price) = fresh {
+                return Some(CachedPrice {
+                    price,
+                    updated_at: now,
+                });
+            }
+        }
+        None
+    }
+
+    async fn read_chainlink(
+        &self,
+        feed: Address,
+        now: u64,
+        max_age_secs: u64,
+        max_rounds_behind: u64,
+    ) -> Result<U256, LiquidationError> {
+        let feed = ChainlinkFeed::new(feed, self.client.clone());
+        let (round_id, answer, _, updated_at, _) = feed
+            .latest_round_data()
+            .call()
+            .await
+            .map_err(|e| NetError(format!("cant read chainlink feed: {}", e)))?;
+        let latest_round_id = feed
+            .latest_round()
+            .call()
+            .await
+            .map_err(|e| NetError(format!("cant read latest round: {}", e)))?;
+
+        if answer <= I256::zero() {
+            return Err(NetError(
+                "chainlink feed returned non-positive answer".into(),
+            ));
+        }
+        if now.saturating_sub(updated_at.as_u64()) > max_age_secs {
+            return Err(NetError("chainlink feed is stale".into()));
+        }
+        if latest_round_id.saturating_sub(round_id) > U256::from(max_rounds_behind) {
+            return Err(NetError("chainlink feed is rounds behind".into()));
+        }
+        Ok(answer.into_raw())
+    }
+
+    async fn read_dex_twap(
+        &self,
+        pool: Address,
+        window_secs: u64,
+    ) -> Result<U256, LiquidationError> {
+        let twap = DexTwap::new(pool, self.client.clone());
+        twap.consult(U256::from(window_secs))
+            .call()
+            .await
+            .map_err(|e| NetError(format!("cant read dex twap: {}", e)))
+    }
+
+    /// Current price for `market`, or `None` if no configured source has
+    /// ever returned a fresh value. Callers should skip liquidation
+    /// evaluation for that market rather than erroring out of the loop.
+    pub fn price(&self, market: &Address) -> Option<U256> {
+        self.prices.get(market).map(|cached| cached.price)
+    }
+}
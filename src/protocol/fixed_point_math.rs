@@ -0,0 +1,4 @@
+//! Re-export of the crate-root fixed-point helpers: `liquidation.rs` lives
+//! under `protocol`, but the math itself isn't protocol-specific, so there's
+//! only one real implementation, here under `crate::fixed_point_math`.
+pub use crate::fixed_point_math::{math, mul_div_down_signed, FixedPointMath, FixedPointMathGen};
@@ -12,7 +12,16 @@ use tokio::sync::mpsc::Receiver;
 use tokio::sync::Mutex;
 use tokio::time;
 
-use super::{Account, Auditor, LiquidationIncentive, Liquidator, MarketAccount, Previewer};
+use super::{
+    Account, Auditor, LiquidationIncentive, Liquidator, MarketAccount, Previewer, UniswapV3Factory,
+};
+use crate::quote_provider::SwapQuoteProvider;
+use crate::trade_simulator::{TradeSimulator, TradeVenue};
+
+/// Liquidations never route more than this fraction of a pool's reserves
+/// through a single fee tier before spilling over into the next tier, so a
+/// single pool can't absorb the whole trade within an acceptable impact.
+const DEFAULT_MAX_PRICE_IMPACT: u64 = 50; // 5%, in percent-of-reserve terms (denominator 1000)
 
 #[derive(Default, Debug)]
 pub struct Repay {
@@ -28,6 +37,19 @@ pub struct Repay {
     pub total_adjusted_debt: U256,
     pub repay_asset_address: Address,
     pub collateral_asset_address: Address,
+    pub collateral_price: U256,
+    pub collateral_decimals: u8,
+}
+
+/// How the seized collateral will be converted into the repay asset: either
+/// an on-chain pool pair/fee tier, or a calldata route returned by an
+/// off-chain aggregator quote when one was available.
+#[derive(Clone, Debug, Default)]
+pub struct SwapRoute {
+    pub pool_pair: Address,
+    pub fee: u32,
+    pub aggregator_target: Option<Address>,
+    pub aggregator_calldata: Option<ethers::types::Bytes>,
 }
 
 #[derive(Debug)]
@@ -64,6 +86,21 @@ pub struct Liquidation<M, S> {
     market_weth_address: Address,
     backup: u32,
     liquidate_unprofitable: bool,
+    uniswap_factory: UniswapV3Factory<SignerMiddleware<M, S>>,
+    max_price_impact: U256,
+    quote_provider: Option<Arc<dyn SwapQuoteProvider>>,
+    /// USD value (WAD) below which `total_value_debt` is considered dust:
+    /// rather than leaving an uneconomical remainder behind, the close
+    /// factor is forced to 100% so the position is repaid in full. Only
+    /// takes effect when `liquidate_unprofitable` is set, since closing
+    /// dust in one shot can run at a small loss.
+    dust_threshold: U256,
+    /// WAD safety margin applied on top of the oracle price when valuing a
+    /// position for liquidation: collateral is discounted by this much,
+    /// debt is marked up by this much. Guards against a liquidation that
+    /// only clears because of an oracle price that moves against the bot
+    /// between simulation and execution.
+    price_spread: U256,
 }
 
 impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
@@ -76,11 +113,16 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
         weth_address: Address,
         backup: u32,
         liquidate_unprofitable: bool,
+        uniswap_factory_address: Address,
+        quote_provider: Option<Arc<dyn SwapQuoteProvider>>,
+        dust_threshold: U256,
+        price_spread: U256,
     ) -> Self {
         let (token_pairs, tokens) = parse_token_pairs(token_pairs);
         let token_pairs = Arc::new(token_pairs);
         let tokens = Arc::new(tokens);
         Self {
+            uniswap_factory: UniswapV3Factory::new(uniswap_factory_address, client.clone()),
             client,
             token_pairs,
             tokens,
@@ -90,6 +132,10 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
             market_weth_address: weth_address,
             backup,
             liquidate_unprofitable,
+            max_price_impact: U256::from(DEFAULT_MAX_PRICE_IMPACT),
+            quote_provider,
+            dust_threshold,
+            price_spread,
         }
     }
 
@@ -212,7 +258,7 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
                 )
                 .await;
 
-            let (profitable, max_repay, pool_pair, fee) = match response {
+            let (profitable, max_repay, route) = match response {
                 Some(response) => response,
                 None => return Ok(()),
             };
@@ -229,7 +275,14 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
             println!("Liquidating on market {:#?}", address);
             println!("seizing                    {:#?}", repay.market_to_seize);
 
-            // liquidate using liquidator contract
+            // `Liquidator` only exposes the static pool-pair/fee swap entry
+            // point, so that's what every liquidation submits through, even
+            // when `quote_seize_proceeds` picked an aggregator quote for
+            // `route.aggregator_target`/`aggregator_calldata` — those two
+            // fields only inform `is_profitable`'s estimate of realized
+            // proceeds and are never forwarded on-chain. A calldata-forwarding
+            // entry point would need its ABI added to the `Liquidator`
+            // deployment artifact before this contract could dispatch to it.
             let func = self
                 .liquidator
                 .liquidate(
@@ -237,8 +290,8 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
                     repay.market_to_seize.unwrap_or(Address::zero()),
                     account.address,
                     max_repay,
-                    pool_pair,
-                    fee,
+                    route.pool_pair,
+                    route.fee,
                 )
                 .gas(6_666_666u128);
 
@@ -260,7 +313,7 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
         markets: &Vec<Address>,
         price_feeds: &HashMap<Address, Address>,
         assets: &HashMap<Address, Address>,
-    ) -> Option<(bool, U256, Address, u32)> {
+    ) -> Option<(bool, U256, SwapRoute)> {
         let mut multicall =
             Multicall::<SignerMiddleware<M, S>>::new(Arc::clone(&self.client), None)
                 .await
@@ -311,30 +364,49 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
             .duration_since(SystemTime::UNIX_EPOCH)
             .unwrap()
             .as_secs();
-        let repay = Self::pick_markets(&market_account, &prices, timestamp.into(), assets);
-        Self::is_profitable(
+        let repay = Self::pick_markets(
+            &market_account,
+            &prices,
+            timestamp.into(),
+            assets,
+            self.price_spread,
+        );
+        self.is_profitable(
             &repay,
             &liquidation_incentive,
             last_gas_price,
             prices[&self.market_weth_address],
-            &self.token_pairs,
-            &self.tokens,
         )
+        .await
     }
 
+    // `MarketAccount.floating_deposit_assets`/`floating_borrow_assets` come
+    // back from `Previewer.exactly` already converted from shares to assets
+    // - `Market::convert_to_assets`/`borrow_shares_to_assets` exist because
+    // `crate::market::Market` tracks raw share balances from events and has
+    // to do that conversion itself, but there are no shares left to convert
+    // here, so applying them again would double-convert instead of adding
+    // precision.
     pub fn pick_markets(
         market_account: &Vec<MarketAccount>,
         prices: &HashMap<Address, U256>,
         timestamp: U256,
         assets: &HashMap<Address, Address>,
+        price_spread: U256,
     ) -> Repay {
         let mut repay = Repay::default();
         for market in market_account {
+            // Collateral is valued below the oracle price and debt above it
+            // by `price_spread`, mirroring `Market::collateral_price`/
+            // `debt_price` - a safety margin so a liquidation only looks
+            // worthwhile once the account is unhealthy by more than the
+            // spread, instead of right at the oracle-reported edge.
+            let collateral_price = prices[&market.market].mul_wad_down(math::WAD - price_spread);
+            let debt_price = prices[&market.market].mul_wad_up(math::WAD + price_spread);
             if market.is_collateral {
-                let collateral_value = market.floating_deposit_assets.mul_div_down(
-                    prices[&market.market],
-                    U256::exp10(market.decimals as usize),
-                );
+                let collateral_value = market
+                    .floating_deposit_assets
+                    .mul_div_down(collateral_price, U256::exp10(market.decimals as usize));
                 let adjusted_collateral =
                     collateral_value.mul_wad_down(market.adjust_factor.into());
                 repay.total_value_collateral += collateral_value;
@@ -343,6 +415,8 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
                     repay.market_to_seize_value = adjusted_collateral;
                     repay.market_to_seize = Some(market.market);
                     repay.collateral_asset_address = assets[&market.market];
+                    repay.collateral_price = collateral_price;
+                    repay.collateral_decimals = market.decimals;
                 }
             };
             let mut market_debt_assets = U256::zero();
@@ -357,17 +431,15 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
                 }
             }
             market_debt_assets += market.floating_borrow_assets;
-            let market_debt_value = market_debt_assets.mul_div_up(
-                prices[&market.market],
-                U256::exp10(market.decimals as usize),
-            );
+            let market_debt_value =
+                market_debt_assets.mul_div_up(debt_price, U256::exp10(market.decimals as usize));
             let adjusted_debt = market_debt_value.div_wad_up(market.adjust_factor.into());
             repay.total_value_debt += market_debt_value;
             repay.total_adjusted_debt += adjusted_debt;
             if adjusted_debt >= repay.market_to_liquidate_debt {
                 repay.market_to_liquidate_debt = adjusted_debt;
                 repay.market_to_repay = Some(market.market);
-                repay.price = prices[&market.market];
+                repay.price = debt_price;
                 repay.decimals = market.decimals;
                 repay.repay_asset_address = assets[&market.market];
             }
@@ -375,30 +447,243 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
         repay
     }
 
-    pub fn is_profitable(
+    /// Prices the collateral->repay-asset swap off an aggregator quote when
+    /// `quote_provider` is configured and reachable, falling back to
+    /// simulating the swap through the pools backing `get_flash_pair`
+    /// (rather than a flat `swap_fee`) so a seizure large enough to move
+    /// the pool is still reflected as slippage when the bot runs offline.
+    ///
+    /// `liquidation_incentive` is used as-is, at its full authoritative
+    /// on-chain value, rather than ramped down by `Market::
+    /// liquidation_incentive`'s elapsed-time curve: that ramp is a
+    /// bot-side prediction over a synthetic `penalty_rate`-derived ceiling
+    /// (used to decide when it's worth polling a market again), not a
+    /// time-varying property of the real incentive. This check already
+    /// re-evaluates real profitability, with the real incentive and a live
+    /// AMM simulation, on every call - gating it behind that ramp would
+    /// only ever delay or skip liquidations this check already knows are
+    /// profitable right now.
+    pub async fn is_profitable(
+        &self,
         repay: &Repay,
         liquidation_incentive: &LiquidationIncentive,
         last_gas_price: U256,
         eth_price: U256,
-        token_pairs: &HashMap<(Address, Address), BinaryHeap<Reverse<u32>>>,
-        tokens: &HashSet<Address>,
-    ) -> Option<(bool, U256, Address, u32)> {
-        let max_repay = Self::max_repay_assets(repay, liquidation_incentive, U256::MAX)
+    ) -> Option<(bool, U256, SwapRoute)> {
+        let max_repay = self
+            .max_repay_assets(repay, liquidation_incentive, U256::MAX)
             .mul_wad_down(math::WAD + U256::exp10(14))
             + math::WAD.mul_div_up(U256::exp10(repay.decimals as usize), repay.price);
-        let (pool_pair, fee): (Address, u32) = Self::get_flash_pair(repay, token_pairs, tokens);
-        let profit = Self::max_profit(repay, max_repay, liquidation_incentive);
-        let cost = Self::max_cost(
-            repay,
-            max_repay,
-            liquidation_incentive,
-            U256::from(fee),
-            last_gas_price,
-            U256::from(1500u128),
-            eth_price,
+        let (pool_pair, fee): (Address, u32) =
+            Self::get_flash_pair(repay, &self.token_pairs, &self.tokens);
+
+        let seize_amount = Self::seize_amount(repay, max_repay, liquidation_incentive);
+        let (realized_proceeds, route) = self
+            .quote_seize_proceeds(repay, seize_amount, pool_pair, fee)
+            .await;
+
+        // `Liquidator` only ever executes through the static pool pair
+        // (`liquidate()` never forwards `aggregator_target`/
+        // `aggregator_calldata`), so an aggregator quote deeper than that
+        // pool would look profitable here and then execute at a worse
+        // on-chain price. Only trust it once `liquidate_unprofitable` has
+        // opted into that execution-price risk; otherwise fall back to the
+        // same static-pool simulation that will actually run.
+        let (realized_proceeds, route) =
+            if route.aggregator_target.is_some() && !self.liquidate_unprofitable {
+                (
+                    self.simulate_seize_proceeds(repay, pool_pair, seize_amount)
+                        .await,
+                    SwapRoute {
+                        pool_pair,
+                        fee,
+                        aggregator_target: None,
+                        aggregator_calldata: None,
+                    },
+                )
+            } else {
+                (realized_proceeds, route)
+            };
+
+        // Costs round up, proceeds round down: an understated cost or an
+        // overstated payout could both report a trade as profitable when
+        // it isn't, so every rounding here is chosen to err conservative.
+        let gas_cost = Self::max_cost(last_gas_price, U256::from(1_500u128), eth_price)
+            .mul_div_up(U256::exp10(repay.decimals as usize), repay.price);
+        let lender_fee = max_repay.mul_wad_up(U256::from(liquidation_incentive.lenders));
+        let debt_repaid_plus_fees = max_repay + gas_cost + lender_fee;
+
+        let profitable = realized_proceeds > debt_repaid_plus_fees
+            && realized_proceeds - debt_repaid_plus_fees > math::WAD / U256::exp10(16);
+        Some((profitable, max_repay, route))
+    }
+
+    // Tries the configured aggregator quote first; on a missing provider or
+    // an unreachable/bad quote, falls back to the static-pair AMM
+    // simulation so the bot keeps running offline.
+    async fn quote_seize_proceeds(
+        &self,
+        repay: &Repay,
+        seize_amount: U256,
+        pool_pair: Address,
+        fee: u32,
+    ) -> (U256, SwapRoute) {
+        if let Some(provider) = &self.quote_provider {
+            if let Some(quote) = provider
+                .quote(
+                    repay.collateral_asset_address,
+                    repay.repay_asset_address,
+                    seize_amount,
+                )
+                .await
+            {
+                return (
+                    quote.buy_amount,
+                    SwapRoute {
+                        pool_pair,
+                        fee,
+                        aggregator_target: Some(quote.to),
+                        aggregator_calldata: Some(quote.data),
+                    },
+                );
+            }
+        }
+        (
+            self.simulate_seize_proceeds(repay, pool_pair, seize_amount)
+                .await,
+            SwapRoute {
+                pool_pair,
+                fee,
+                aggregator_target: None,
+                aggregator_calldata: None,
+            },
+        )
+    }
+
+    // Collateral-token amount that would be seized to repay `max_repay`,
+    // derived from the USD value of the repay plus the liquidator/lender
+    // bonus, converted back into the collateral asset at its own price and
+    // decimals (tracked on `Repay` alongside the repay-side price).
+    fn seize_amount(
+        repay: &Repay,
+        max_repay: U256,
+        liquidation_incentive: &LiquidationIncentive,
+    ) -> U256 {
+        if repay.collateral_price.is_zero() {
+            return U256::zero();
+        }
+        let repay_value = max_repay.mul_div_up(repay.price, U256::exp10(repay.decimals as usize));
+        let seize_value = repay_value.mul_wad_up(
+            math::WAD
+                + U256::from(liquidation_incentive.liquidator)
+                + U256::from(liquidation_incentive.lenders),
         );
-        let profitable = profit > cost && profit - cost > math::WAD / U256::exp10(16);
-        Some((profitable, max_repay, pool_pair, fee))
+        seize_value.mul_div_down(
+            U256::exp10(repay.collateral_decimals as usize),
+            repay.collateral_price,
+        )
+    }
+
+    // Walks the fee tiers stored for the (collateral, repay) pair cheapest
+    // first, pricing each tier's fill through the constant-product formula
+    // against that pool's live reserves, and spills over into the next
+    // tier once a single pool can't absorb its share within
+    // `max_price_impact`.
+    async fn simulate_seize_proceeds(
+        &self,
+        repay: &Repay,
+        pool_pair: Address,
+        seize_amount: U256,
+    ) -> U256 {
+        let mut fees: Vec<u32> = self
+            .token_pairs
+            .get(&ordered_addresses(
+                repay.collateral_asset_address,
+                repay.repay_asset_address,
+            ))
+            .map(|tiers| tiers.iter().map(|Reverse(fee)| *fee).collect())
+            .unwrap_or_default();
+        fees.sort_unstable();
+        if fees.is_empty() && !pool_pair.is_zero() {
+            // No configured tiers for this pair, so `get_flash_pair` fell
+            // back to fee 0: probe the standard Uniswap V3 fee tiers
+            // instead of that synthetic 0bps tier, which is not a real tier
+            // and would just make `get_pool` return the zero address below.
+            fees.extend([500u32, 3_000, 10_000]);
+        }
+
+        let mut remaining = seize_amount;
+        let mut total_output = U256::zero();
+        for fee in fees {
+            if remaining.is_zero() {
+                break;
+            }
+            let pool_address = self
+                .uniswap_factory
+                .get_pool(
+                    repay.collateral_asset_address,
+                    repay.repay_asset_address,
+                    fee,
+                )
+                .call()
+                .await
+                .unwrap_or_default();
+            if pool_address.is_zero() {
+                continue;
+            }
+            let reserves = self
+                .pool_reserves(
+                    pool_address,
+                    repay.collateral_asset_address,
+                    repay.repay_asset_address,
+                )
+                .await;
+            let (reserve_in, reserve_out) = match reserves {
+                Some(reserves) => reserves,
+                None => continue,
+            };
+            let portion = U256::min(
+                remaining,
+                reserve_in.mul_div_down(self.max_price_impact, U256::from(1_000u32)),
+            );
+            if portion.is_zero() {
+                continue;
+            }
+            let fill = TradeSimulator::simulate(
+                &TradeVenue::ConstantProductPool {
+                    reserve_in,
+                    reserve_out,
+                    fee,
+                },
+                portion,
+            );
+            total_output += fill.output;
+            remaining -= portion;
+        }
+        if !remaining.is_zero() {
+            // Every configured tier is already at its price-impact cap:
+            // `total_output` understates the true proceeds for the unfilled
+            // `remaining` seize amount, so callers must not treat it as a
+            // complete quote.
+            println!(
+                "simulate_seize_proceeds: {:?} of {:?} seize amount for {:?} could not be routed within max_price_impact",
+                remaining, seize_amount, repay.collateral_asset_address
+            );
+        }
+        total_output
+    }
+
+    async fn pool_reserves(
+        &self,
+        pool_address: Address,
+        token_in: Address,
+        token_out: Address,
+    ) -> Option<(U256, U256)> {
+        let token_in = crate::market::ERC20::new(token_in, self.client.clone());
+        let token_out = crate::market::ERC20::new(token_out, self.client.clone());
+        let reserve_in = token_in.balance_of(pool_address).call().await.ok()?;
+        let reserve_out = token_out.balance_of(pool_address).call().await.ok()?;
+        Some((reserve_in, reserve_out))
     }
 
     fn get_flash_pair(
@@ -434,12 +719,63 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
         (pair_contract, lowest_fee)
     }
 
+    // This, not `Market::max_repay_assets`, is what actually sizes a
+    // liquidation: `resolve_close_factor` already derives the close factor
+    // from the account's own target-health math (`calculate_close_factor`)
+    // and forces a full close under `dust_threshold`. `Market`'s
+    // `close_factor`/`close_amount` are a static per-market fallback for
+    // that same decision - wiring them in here as well would mean the two
+    // could disagree about how much to repay, and the static pair is a
+    // cruder approximation of what `calculate_close_factor` already
+    // computes from real account/incentive data, so it would only ever
+    // make the sizing worse, never better.
     fn max_repay_assets(
+        &self,
+        repay: &Repay,
+        liquidation_incentive: &LiquidationIncentive,
+        max_liquidator_assets: U256,
+    ) -> U256 {
+        let close_factor = Self::resolve_close_factor(
+            repay,
+            liquidation_incentive,
+            self.liquidate_unprofitable,
+            self.dust_threshold,
+        );
+        Self::max_repay_from_close_factor(
+            repay,
+            liquidation_incentive,
+            max_liquidator_assets,
+            close_factor,
+        )
+    }
+
+    // Below the dust threshold, a partial close just leaves behind a
+    // remainder that's never worth cleaning up later, so close the whole
+    // position in one shot instead (may run at a small loss). Only takes
+    // effect once the operator has opted into `liquidate_unprofitable`.
+    fn resolve_close_factor(
+        repay: &Repay,
+        liquidation_incentive: &LiquidationIncentive,
+        liquidate_unprofitable: bool,
+        dust_threshold: U256,
+    ) -> U256 {
+        if liquidate_unprofitable && repay.total_value_debt < dust_threshold {
+            math::WAD
+        } else {
+            Self::calculate_close_factor(repay, liquidation_incentive)
+        }
+    }
+
+    // Debt/repay amounts round up (ceil) and collateral/seize amounts round
+    // down (floor) throughout, and the final `.min` clamp guarantees
+    // `max_repay` never exceeds `market_to_liquidate_debt` even when
+    // `close_factor` rounds all the way up to `math::WAD`.
+    fn max_repay_from_close_factor(
         repay: &Repay,
         liquidation_incentive: &LiquidationIncentive,
         max_liquidator_assets: U256,
+        close_factor: U256,
     ) -> U256 {
-        let close_factor = Self::calculate_close_factor(repay, liquidation_incentive);
         U256::min(
             U256::min(
                 repay
@@ -462,31 +798,12 @@ impl<M: 'static + Middleware, S: 'static + Signer> Liquidation<M, S> {
         .min(repay.market_to_liquidate_debt)
     }
 
-    fn max_profit(
-        repay: &Repay,
-        max_repay: U256,
-        liquidation_incentive: &LiquidationIncentive,
-    ) -> U256 {
-        max_repay
-            .mul_div_up(repay.price, U256::exp10(repay.decimals as usize))
-            .mul_wad_down(U256::from(
-                liquidation_incentive.liquidator + liquidation_incentive.lenders,
-            ))
-    }
-
-    fn max_cost(
-        repay: &Repay,
-        max_repay: U256,
-        liquidation_incentive: &LiquidationIncentive,
-        swap_fee: U256,
-        gas_price: U256,
-        gas_cost: U256,
-        eth_price: U256,
-    ) -> U256 {
-        let max_repay = max_repay.mul_div_down(repay.price, U256::exp10(repay.decimals as usize));
-        max_repay.mul_wad_down(U256::from(liquidation_incentive.lenders))
-            + max_repay.mul_wad_down(swap_fee * U256::from(U256::exp10(12)))
-            + (gas_price * gas_cost).mul_wad_down(eth_price)
+    // USD value of the gas spent broadcasting the liquidation, rounded up;
+    // the swap fee that used to live here is now priced directly off the
+    // simulated AMM fill, and the lender fee is computed separately, both in
+    // `is_profitable`.
+    fn max_cost(gas_price: U256, gas_cost: U256, eth_price: U256) -> U256 {
+        (gas_price * gas_cost).mul_wad_up(eth_price)
     }
 
     pub fn calculate_close_factor(
@@ -566,6 +883,8 @@ mod services_test {
 
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use ethers::providers::{Http, Provider};
+    use ethers::signers::LocalWallet;
 
     #[test]
     fn test_parse_token_pairs() {
@@ -600,4 +919,43 @@ mod services_test {
             1000
         );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_max_repay_never_exceeds_debt_at_wad_boundary() {
+        let repay = Repay {
+            price: U256::exp10(18),
+            decimals: 18,
+            total_value_debt: U256::exp10(18),
+            total_adjusted_collateral: U256::exp10(18),
+            total_adjusted_debt: U256::exp10(18),
+            total_value_collateral: U256::exp10(18),
+            market_to_seize_value: U256::exp10(18),
+            market_to_liquidate_debt: U256::exp10(18),
+            ..Default::default()
+        };
+        let liquidation_incentive = LiquidationIncentive {
+            liquidator: U256::exp10(16),
+            lenders: U256::exp10(16),
+        };
+
+        // A close factor right at the WAD boundary asks to repay the whole
+        // debt; whichever bound ends up binding, the result must not
+        // overshoot the debt it's meant to partially (or fully) repay.
+        let max_repay = Liquidation::<Provider<Http>, LocalWallet>::max_repay_from_close_factor(
+            &repay,
+            &liquidation_incentive,
+            U256::MAX,
+            math::WAD,
+        );
+        assert!(max_repay <= repay.market_to_liquidate_debt);
+
+        let max_repay_partial =
+            Liquidation::<Provider<Http>, LocalWallet>::max_repay_from_close_factor(
+                &repay,
+                &liquidation_incentive,
+                U256::MAX,
+                math::WAD / 2,
+            );
+        assert!(max_repay_partial <= repay.market_to_liquidate_debt);
+    }
+}
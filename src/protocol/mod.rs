@@ -59,4 +59,16 @@ abigen!(
     Liquidator,
     "deployments/rinkeby/Liquidator.json",
     event_derives(serde::Deserialize, serde::Serialize)
+);
+
+abigen!(
+    UniswapV3Pool,
+    "node_modules/@uniswap/v3-core/artifacts/contracts/UniswapV3Pool.sol/UniswapV3Pool.json",
+    event_derives(serde::Deserialize, serde::Serialize)
+);
+
+abigen!(
+    UniswapV3Factory,
+    "node_modules/@uniswap/v3-core/artifacts/contracts/UniswapV3Factory.sol/UniswapV3Factory.json",
+    event_derives(serde::Deserialize, serde::Serialize)
 );
\ No newline at end of file
@@ -0,0 +1,80 @@
+use async_trait::async_trait;
+use ethers::types::{Address, Bytes, U256};
+use serde::Deserialize;
+
+/// A realized swap route priced by an off-chain aggregator, used in place of
+/// the on-chain constant-product estimate whenever a live quote is
+/// available; it is not compared against the statically configured pools.
+#[derive(Clone, Debug)]
+pub struct SwapQuote {
+    pub buy_amount: U256,
+    pub to: Address,
+    pub data: Bytes,
+}
+
+/// Queried from `Liquidation::is_profitable` to price the
+/// collateral->repay-asset leg off-chain, the way 0x/CoW-style aggregators
+/// expose a `/swap/quote` endpoint. Implementors should return `None`
+/// (rather than erroring) on any failure so callers can fall back to the
+/// static-pair AMM simulation.
+#[async_trait]
+pub trait SwapQuoteProvider: Send + Sync {
+    async fn quote(
+        &self,
+        sell_token: Address,
+        buy_token: Address,
+        sell_amount: U256,
+    ) -> Option<SwapQuote>;
+}
+
+#[derive(Deserialize)]
+struct AggregatorResponse {
+    #[serde(rename = "buyAmount")]
+    buy_amount: String,
+    to: Address,
+    data: Bytes,
+}
+
+/// Queries a 0x/CoW-compatible `/swap/quote` endpoint for the realistic
+/// output of selling `sell_amount` of `sell_token` into `buy_token`.
+pub struct AggregatorQuoteProvider {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl AggregatorQuoteProvider {
+    pub fn new(base_url: String) -> Self {
+        Self {
+            base_url,
+            client: reqwest::Client::new(),
+        }
+    }
+}
+
+#[async_trait]
+impl SwapQuoteProvider for AggregatorQuoteProvider {
+    async fn quote(
+        &self,
+        sell_token: Address,
+        buy_token: Address,
+        sell_amount: U256,
+    ) -> Option<SwapQuote> {
+        let response = self
+            .client
+            .get(format!("{}/swap/quote", self.base_url))
+            .query(&[
+                ("sellToken", format!("{:?}", sell_token)),
+                ("buyToken", format!("{:?}", buy_token)),
+                ("sellAmount", sell_amount.to_string()),
+            ])
+            .send()
+            .await
+            .ok()?;
+        let body: AggregatorResponse = response.json().await.ok()?;
+        Some(SwapQuote {
+            buy_amount: U256::from_dec_str(&body.buy_amount).ok()?,
+            to: body.to,
+            data: body.data,
+        })
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use ethers::prelude::*;
+use ethers::types::{Address, U256};
+
+use crate::bindings::Terminator;
+use crate::errors::LiquidationError;
+use crate::errors::LiquidationError::NetError;
+use crate::path_finder::PathFinder;
+
+/// Number of blocks a submitted liquidation may sit pending before its tip
+/// is escalated via replacement-by-fee.
+const ESCALATION_BLOCKS: u64 = 2;
+/// Hard ceiling on how many times a liquidation's tip can be escalated.
+const MAX_ESCALATIONS: u32 = 5;
+/// Default multiplier applied to the base-fee-history tip when computing
+/// the initial bid.
+const DEFAULT_TIP_MULTIPLIER: u32 = 2;
+/// Bids never exceed this fraction (in basis points) of the expected
+/// liquidation profit, regardless of how many times they've escalated.
+const MAX_BID_BPS: u64 = 2_000; // 20%
+/// Gas limit every liquidation transaction is submitted with, used both to
+/// cap the per-gas bid against `expected_profit` and to estimate total gas
+/// cost.
+const GAS_LIMIT: u128 = 6_666_666;
+/// How often `liquidate` polls for a receipt while waiting out
+/// `ESCALATION_BLOCKS`.
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(2);
+
+#[derive(Clone, Debug)]
+pub struct TerminatorJob {
+    pub credit_manager: Address,
+    pub underlying_token: Address,
+    pub borrower: Address,
+    pub repay_amount: U256,
+    pub pool_pair: Address,
+    pub fee: u32,
+    pub expected_profit: U256,
+}
+
+/// The gas parameters chosen for a liquidation attempt, surfaced in the
+/// AMQP notifications so operators can see what the bot actually bid.
+#[derive(Clone, Copy, Debug)]
+pub struct GasBid {
+    pub max_fee_per_gas: U256,
+    pub max_priority_fee_per_gas: U256,
+    pub escalation: u32,
+}
+
+impl GasBid {
+    /// `expected_profit` is a total wei amount; `max_fee_per_gas`/
+    /// `max_priority_fee_per_gas` are per-gas-unit prices, so the cap has to
+    /// divide the profit-derived wei budget by `GAS_LIMIT` before comparing
+    /// it against a per-gas price, or it caps a per-gas price against a
+    /// total-wei figure and almost never actually binds.
+    fn capped(self, expected_profit: U256) -> Self {
+        let cap = expected_profit * U256::from(MAX_BID_BPS)
+            / U256::from(10_000u32)
+            / U256::from(GAS_LIMIT);
+        GasBid {
+            max_fee_per_gas: U256::min(self.max_fee_per_gas, cap),
+            max_priority_fee_per_gas: U256::min(self.max_priority_fee_per_gas, cap),
+            escalation: self.escalation,
+        }
+    }
+
+    fn escalate(self) -> Self {
+        GasBid {
+            max_fee_per_gas: self.max_fee_per_gas + self.max_fee_per_gas / 8u32,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas
+                + self.max_priority_fee_per_gas / 8u32,
+            escalation: self.escalation + 1,
+        }
+    }
+}
+
+/// Result of a dry-run liquidation: the collateral it would seize and the
+/// profit expected after swapping that collateral back to the repay asset
+/// and paying gas, per [`PathFinder`]'s quoted route.
+#[derive(Clone, Copy, Debug)]
+pub struct SimulationResult {
+    pub seized_collateral: U256,
+    pub expected_net_profit: U256,
+}
+
+pub struct TerminatorService<M: Middleware, S: Signer> {
+    terminator: Terminator<SignerMiddleware<M, S>>,
+    terminator_flash: Terminator<SignerMiddleware<M, S>>,
+    client: Arc<SignerMiddleware<M, S>>,
+    enabled: bool,
+    tip_multiplier: u32,
+}
+
+impl<M: 'static + Middleware, S: 'static + Signer> TerminatorService<M, S> {
+    pub async fn new(
+        terminator_address: &Address,
+        terminator_flash_address: &Address,
+        client: Arc<SignerMiddleware<M, S>>,
+        enabled: bool,
+    ) -> Self {
+        Self {
+            terminator: Terminator::new(*terminator_address, client.clone()),
+            terminator_flash: Terminator::new(*terminator_flash_address, client.clone()),
+            client,
+            enabled,
+            tip_multiplier: DEFAULT_TIP_MULTIPLIER,
+        }
+    }
+
+    /// Computes `maxFeePerGas`/`maxPriorityFeePerGas` from `eth_feeHistory`
+    /// plus the configured tip multiplier, capped at a fraction of
+    /// `expected_profit` so a bidding war never turns a liquidation
+    /// unprofitable.
+    async fn bid_gas(&self, expected_profit: U256) -> Result<GasBid, LiquidationError> {
+        let fee_history = self
+            .client
+            .provider()
+            .fee_history(1u64, BlockNumber::Latest, &[50.0])
+            .await
+            .map_err(|e| NetError(format!("cant fetch fee history: {}", e)))?;
+
+        let base_fee = *fee_history.base_fee_per_gas.last().unwrap_or(&U256::zero());
+        let priority_fee = fee_history
+            .reward
+            .last()
+            .and_then(|rewards| rewards.get(0))
+            .copied()
+            .unwrap_or_else(U256::zero);
+
+        let max_priority_fee_per_gas = priority_fee * self.tip_multiplier;
+        let max_fee_per_gas = base_fee * 2u32 + max_priority_fee_per_gas;
+
+        Ok(GasBid {
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
+            escalation: 0,
+        }
+        .capped(expected_profit))
+    }
+
+    /// Statically calls the liquidation before broadcasting it, to catch
+    /// reverts (price moved, account already healthy, slippage) without
+    /// burning gas, and to read back the seized collateral so its realized
+    /// value net of the swap path and gas bid can be checked against
+    /// `min_profit` before sending anything on-chain.
+    pub async fn simulate(
+        &self,
+        job: &TerminatorJob,
+        terminator_type: u32,
+        path_finder: &PathFinder<SignerMiddleware<M, S>>,
+        min_profit: U256,
+    ) -> Result<SimulationResult, String> {
+        let terminator = if terminator_type == 2 {
+            &self.terminator_flash
+        } else {
+            &self.terminator
+        };
+
+        let seized_collateral = terminator
+            .liquidate(job.borrower, job.credit_manager, job.repay_amount)
+            .call()
+            .await
+            .map_err(|e| format!("simulation reverted: {}", e))?;
+
+        let swap_proceeds = path_finder
+            .quote(job.pool_pair, job.underlying_token, seized_collateral)
+            .await
+            .map_err(|e| format!("cant quote swap path: {}", e))?;
+
+        let gas_bid = self
+            .bid_gas(job.expected_profit)
+            .await
+            .map_err(|e| format!("cant bid gas: {}", e))?;
+        let gas_cost = gas_bid.max_fee_per_gas * U256::from(GAS_LIMIT);
+
+        let expected_net_profit = swap_proceeds
+            .saturating_sub(job.repay_amount)
+            .saturating_sub(gas_cost);
+
+        if expected_net_profit < min_profit {
+            return Err(format!(
+                "expected net profit {} below min_profit {}",
+                expected_net_profit, min_profit
+            ));
+        }
+
+        Ok(SimulationResult {
+            seized_collateral,
+            expected_net_profit,
+        })
+    }
+
+    /// Sends the liquidation, bidding gas from `eth_feeHistory`, and
+    /// escalates the tip via replacement-by-fee if it isn't mined within
+    /// `ESCALATION_BLOCKS`, up to `MAX_ESCALATIONS` resubmissions.
+    pub async fn liquidate(
+        &self,
+        job: &TerminatorJob,
+        terminator_type: u32,
+    ) -> Result<TransactionReceipt, LiquidationError> {
+        let terminator = if terminator_type == 2 {
+            &self.terminator_flash
+        } else {
+            &self.terminator
+        };
+
+        let mut bid = self.bid_gas(job.expected_profit).await?;
+        let nonce = self
+            .client
+            .get_transaction_count(self.client.address(), None)
+            .await
+            .map_err(|e| NetError(format!("cant fetch nonce: {}", e)))?;
+
+        loop {
+            let tx_hash = terminator
+                .liquidate(job.borrower, job.credit_manager, job.repay_amount)
+                .gas(GAS_LIMIT)
+                .nonce(nonce)
+                .max_fee_per_gas(bid.max_fee_per_gas)
+                .max_priority_fee_per_gas(bid.max_priority_fee_per_gas)
+                .send()
+                .await
+                .map_err(|e| NetError(format!("cant send liquidation: {}", e)))?
+                .tx_hash();
+
+            let submitted_at = self
+                .client
+                .get_block_number()
+                .await
+                .map_err(|e| NetError(format!("cant fetch block number: {}", e)))?;
+            let deadline = submitted_at + ESCALATION_BLOCKS;
+
+            // `PendingTransaction::confirmations` only resolves to `None` if
+            // the tx is dropped or replaced from the mempool, never just
+            // because it's still pending - so escalation has to be driven
+            // off polling for a receipt against a block-height deadline
+            // instead of awaiting confirmations directly.
+            let receipt = loop {
+                if let Some(receipt) = self
+                    .client
+                    .get_transaction_receipt(tx_hash)
+                    .await
+                    .map_err(|e| NetError(format!("cant fetch receipt: {}", e)))?
+                {
+                    break Some(receipt);
+                }
+                if self
+                    .client
+                    .get_block_number()
+                    .await
+                    .map_err(|e| NetError(format!("cant fetch block number: {}", e)))?
+                    >= deadline
+                {
+                    break None;
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+            };
+
+            match receipt {
+                Some(receipt) => return Ok(receipt),
+                None if bid.escalation < MAX_ESCALATIONS => {
+                    println!(
+                        "liquidation for {:?} not mined in {} blocks, bidding higher (attempt {})",
+                        job.borrower,
+                        ESCALATION_BLOCKS,
+                        bid.escalation + 1
+                    );
+                    bid = bid.escalate().capped(job.expected_profit);
+                }
+                None => {
+                    return Err(NetError(format!(
+                        "liquidation for {:?} not mined after {} escalations",
+                        job.borrower, MAX_ESCALATIONS
+                    )))
+                }
+            }
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+}
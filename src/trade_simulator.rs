@@ -0,0 +1,96 @@
+use ethers::types::U256;
+
+use crate::fixed_point_math::{FixedPointMath, FixedPointMathGen};
+
+/// A single price level in an order book, consumed in order when walking a
+/// fill so deeper levels only get hit once shallower ones are exhausted.
+#[derive(Clone, Copy, Debug)]
+pub struct PriceLevel {
+    pub quantity: U256,
+    pub price: U256, // price of 1 unit of input, in WAD
+}
+
+/// A venue the bot could route seized collateral through to realize it as
+/// the repay asset.
+#[derive(Clone, Debug)]
+pub enum TradeVenue {
+    OrderBook(Vec<PriceLevel>),
+    ConstantProductPool {
+        reserve_in: U256,
+        reserve_out: U256,
+        /// Pool fee in hundredths of a basis point (e.g. 3000 = 0.3%, the
+        /// same units Uniswap V3 fee tiers use), subtracted from `dx`
+        /// before the swap.
+        fee: u32,
+    },
+}
+
+#[derive(Clone, Copy, Default, Debug)]
+pub struct FillResult {
+    pub filled_input: U256,
+    pub output: U256,
+    pub worst_fill_price: U256,
+}
+
+/// Estimates what seized collateral will actually fetch when swapped back
+/// to the repay asset, accounting for market depth rather than trusting the
+/// oracle mid price.
+pub struct TradeSimulator;
+
+impl TradeSimulator {
+    /// Consumes `input_quantity` of collateral against `venue`, returning
+    /// the realized output and the worst price actually hit.
+    pub fn simulate(venue: &TradeVenue, input_quantity: U256) -> FillResult {
+        match venue {
+            TradeVenue::OrderBook(levels) => Self::walk_order_book(levels, input_quantity),
+            TradeVenue::ConstantProductPool {
+                reserve_in,
+                reserve_out,
+                fee,
+            } => Self::walk_constant_product(*reserve_in, *reserve_out, *fee, input_quantity),
+        }
+    }
+
+    fn walk_order_book(levels: &[PriceLevel], input_quantity: U256) -> FillResult {
+        let mut remaining = input_quantity;
+        let mut output = U256::zero();
+        let mut worst_fill_price = U256::zero();
+        for level in levels {
+            if remaining.is_zero() {
+                break;
+            }
+            let filled = U256::min(remaining, level.quantity);
+            output += filled.mul_wad_down(level.price);
+            worst_fill_price = level.price;
+            remaining -= filled;
+        }
+        FillResult {
+            filled_input: input_quantity - remaining,
+            output,
+            worst_fill_price,
+        }
+    }
+
+    // Constant-product swap with fee, in the 1e6-precision Uniswap V3 fee
+    // tiers use: dy = (dx*(1e6-fee)*reserveOut) / (reserveIn*1e6 + dx*(1e6-fee)).
+    fn walk_constant_product(reserve_in: U256, reserve_out: U256, fee: u32, dx: U256) -> FillResult {
+        const FEE_PRECISION: u32 = 1_000_000;
+        let dx_after_fee = dx * U256::from(FEE_PRECISION - fee);
+        let output = (dx_after_fee * reserve_out) / (reserve_in * U256::from(FEE_PRECISION) + dx_after_fee);
+        // Worst price hit is the marginal price at the end of the fill:
+        // (1-fee) * (reserveOut - dy) / (reserveIn + dx*(1-fee)), in WAD.
+        let worst_fill_price = if dx.is_zero() {
+            U256::zero()
+        } else {
+            ((reserve_out - output) * U256::from(FEE_PRECISION - fee)).mul_div_down(
+                U256::exp10(18),
+                reserve_in * U256::from(FEE_PRECISION) + dx_after_fee,
+            )
+        };
+        FillResult {
+            filled_input: dx,
+            output,
+            worst_fill_price,
+        }
+    }
+}